@@ -1,30 +1,52 @@
-use std::{cell::RefCell, any::Any};
-
 use rustc_hash::FxHashMap;
 
-use crate::Ast;
+use crate::{Ast, AstNode, Parser};
+use crate::error::{LispError, Span};
 
 type ArgStack<'a> = Vec<EnvType<'a>>;
 
+// collect once the env table reaches this many live envs
+const GC_THRESHOLD: usize = 1024;
+
 #[derive(Debug)]
 pub struct ProcInfo<'a> {
-    args: u8,
+    args: Vec<String>,
     body: &'a Ast,
     captured: EnvId,
 }
 
 impl<'a> Clone for ProcInfo<'a> {
     fn clone(&self) -> Self {
-        Self { args: self.args.clone(), body: self.body, captured: self.captured.clone() }
+        Self { args: self.args.clone(), body: self.body, captured: self.captured }
+    }
+}
+
+impl<'a> ProcInfo<'a> {
+    pub fn new(args: Vec<String>, body: &'a Ast, captured: EnvId) -> Self {
+        Self { args, body, captured }
+    }
+
+    pub fn args(&self) -> &Vec<String> {
+        &self.args
+    }
+
+    pub fn body(&self) -> &'a Ast {
+        self.body
+    }
+
+    pub fn captured(&self) -> EnvId {
+        self.captured
     }
 }
 
 #[derive(Debug, Clone)]
 pub enum EnvType<'a> {
     Number(f64),
-    Proc(String, ProcInfo<'a>),
+    Bool(bool),
+    Symbol(String),
+    List(Vec<EnvType<'a>>),
+    Proc(ProcInfo<'a>),
     NativeProc(String),
-    // List(Vec<EnvType>),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -34,7 +56,13 @@ pub struct EnvId(usize);
 pub struct EnvManager<'a> {
     parents: FxHashMap<usize, EnvId>,
     envs: FxHashMap<usize, Env<'a>>,
-    counter: usize
+    counter: usize,
+    // envs currently being evaluated somewhere up the native call stack, i.e. the roots `collect`
+    // is run with; see `push_active`/`set_active_top`/`pop_active`
+    active: Vec<EnvId>,
+    // envs reachable only through a value (or a brand-new scope) sitting in a native local
+    // variable, not yet stored into any `Env`'s variables; see `push_value_root`/`push_root_id`
+    value_roots: Vec<EnvId>,
 }
 
 impl<'a> EnvManager<'a> {
@@ -43,6 +71,8 @@ impl<'a> EnvManager<'a> {
             parents: FxHashMap::default(),
             envs: FxHashMap::default(),
             counter: 0,
+            active: Vec::new(),
+            value_roots: Vec::new(),
         }
     }
 
@@ -69,6 +99,9 @@ impl<'a> EnvManager<'a> {
 
     pub fn new_env(&mut self, parent: Option<EnvId>) -> EnvId {
         let id = self.counter;
+        if let Some(parent) = parent {
+            self.parents.insert(id, parent);
+        }
         let env = Env::new(EnvId(id), parent);
         self.envs.insert(id, env);
         self.counter += 1;
@@ -80,6 +113,98 @@ impl<'a> EnvManager<'a> {
         self.get_mut(&env).std();
         env
     }
+
+    pub fn env_count(&self) -> usize {
+        self.envs.len()
+    }
+
+    // `Parser::eval` pushes the env it's about to evaluate in and pops it before returning, so
+    // the active stack always reflects every env still referenced by a native call frame above us.
+    pub fn push_active(&mut self, id: EnvId) {
+        self.active.push(id);
+    }
+
+    pub fn pop_active(&mut self) {
+        self.active.pop();
+    }
+
+    // the trampoline in `Parser::eval` reduces onto a new scope without recursing, so it updates
+    // the top of the active stack in place instead of pushing another frame
+    pub fn set_active_top(&mut self, id: EnvId) {
+        if let Some(top) = self.active.last_mut() {
+            *top = id;
+        }
+    }
+
+    // root an env directly, e.g. a scope that's been allocated but not yet reachable via
+    // `active` (still being populated with its args). pair with `truncate_value_roots`.
+    pub fn push_root_id(&mut self, id: EnvId) {
+        self.value_roots.push(id);
+    }
+
+    // root whatever envs a value still sitting in a native local variable keeps alive, e.g. an
+    // argument that's been evaluated but not yet bound into its callee's scope. pair with
+    // `truncate_value_roots` once the value has been stored into an `Env` (or discarded).
+    pub fn push_value_root(&mut self, val: &EnvType<'a>) {
+        match val {
+            EnvType::Proc(proc) => self.value_roots.push(proc.captured()),
+            EnvType::List(items) => items.iter().for_each(|item| self.push_value_root(item)),
+            EnvType::Number(_) | EnvType::Bool(_) | EnvType::Symbol(_) | EnvType::NativeProc(_) => {},
+        }
+    }
+
+    pub fn value_roots_len(&self) -> usize {
+        self.value_roots.len()
+    }
+
+    pub fn truncate_value_roots(&mut self, len: usize) {
+        self.value_roots.truncate(len);
+    }
+
+    // run `collect` rooted at the active stack plus whatever's been pinned with
+    // `push_root_id`/`push_value_root`, once the env table reaches `GC_THRESHOLD`
+    pub fn maybe_collect(&mut self) {
+        if self.envs.len() >= GC_THRESHOLD {
+            let mut roots = self.active.clone();
+            roots.extend_from_slice(&self.value_roots);
+            self.collect(&roots);
+        }
+    }
+
+    // mark-and-sweep collector. roots are envs reachable without going through another env's
+    // storage: the root/std env, and anything the caller is still holding directly (e.g. a scope
+    // currently being evaluated, or a `Proc` value sitting on the native call stack). from each
+    // root we mark transitively by following the `parent` chain and the `captured` env of any
+    // `Proc` found in that env's variables, then sweep every env that was never marked.
+    pub fn collect(&mut self, roots: &[EnvId]) {
+        let mut marked = std::collections::HashSet::new();
+        let mut stack: Vec<usize> = roots.iter().map(|id| id.0).collect();
+
+        while let Some(id) = stack.pop() {
+            if !marked.insert(id) {
+                continue;
+            }
+            if let Some(parent) = self.parents.get(&id) {
+                stack.push(parent.0);
+            }
+            if let Some(env) = self.envs.get(&id) {
+                for val in env.variables.values() {
+                    Self::mark_value(val, &mut stack);
+                }
+            }
+        }
+
+        self.envs.retain(|id, _| marked.contains(id));
+        self.parents.retain(|id, _| marked.contains(id));
+    }
+
+    fn mark_value(val: &EnvType<'a>, stack: &mut Vec<usize>) {
+        match val {
+            EnvType::Proc(proc) => stack.push(proc.captured().0),
+            EnvType::List(items) => items.iter().for_each(|item| Self::mark_value(item, stack)),
+            EnvType::Number(_) | EnvType::Bool(_) | EnvType::Symbol(_) | EnvType::NativeProc(_) => {},
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -96,7 +221,7 @@ impl<'a> Env<'a> {
 
     pub fn std(&mut self) {
         let f = [
-            "*"
+            "+", "-", "*", "/", "=", "<", ">", "<=", ">=", "eval", "apply",
         ];
         for i in f {
             self.set(i.to_string(), EnvType::NativeProc(i.to_string()));
@@ -104,21 +229,84 @@ impl<'a> Env<'a> {
         self.set("pi".to_string(), EnvType::Number(std::f64::consts::PI));
     }
 
-    pub fn native_call(name: &String, args: ArgStack<'a>) -> Result<EnvType<'a>, String> {
+    fn as_number<'b>(val: &EnvType<'b>, span: Span) -> Result<f64, LispError> {
+        match val {
+            EnvType::Number(n) => Ok(*n),
+            _ => Err(LispError::new("expected a number", span)),
+        }
+    }
+
+    pub fn native_call(name: &String, args: ArgStack<'a>, env_manager: &mut EnvManager<'a>, env_id: EnvId, span: Span) -> Result<EnvType<'a>, LispError> {
         match name.as_str() {
+            "+" => {
+                let nums = args.iter().map(|v| Self::as_number(v, span)).collect::<Result<Vec<_>, _>>()?;
+                Ok(EnvType::Number(nums.iter().sum()))
+            },
+            "-" => {
+                let nums = args.iter().map(|v| Self::as_number(v, span)).collect::<Result<Vec<_>, _>>()?;
+                match &nums[..] {
+                    [] => Err(LispError::new("- requires at least one argument", span)),
+                    [a] => Ok(EnvType::Number(-a)),
+                    [first, rest @ ..] => Ok(EnvType::Number(rest.iter().fold(*first, |acc, n| acc - n))),
+                }
+            },
             "*" => {
-                let [a, b] = &args[..] else { return Err("incorrect args".to_string()); };
-                let a = match a {
-                    EnvType::Number(a) => Ok(a),
-                    _ => Err("not number".to_string())
-                }?;
-                let b = match b {
-                    EnvType::Number(a) => Ok(a),
-                    _ => Err("not number".to_string())
-                }?;
-                Ok(EnvType::Number(a * b))
+                let nums = args.iter().map(|v| Self::as_number(v, span)).collect::<Result<Vec<_>, _>>()?;
+                Ok(EnvType::Number(nums.iter().product()))
+            },
+            "/" => {
+                let nums = args.iter().map(|v| Self::as_number(v, span)).collect::<Result<Vec<_>, _>>()?;
+                match &nums[..] {
+                    [] => Err(LispError::new("/ requires at least one argument", span)),
+                    [a] => Ok(EnvType::Number(1.0 / a)),
+                    [first, rest @ ..] => Ok(EnvType::Number(rest.iter().fold(*first, |acc, n| acc / n))),
+                }
+            },
+            "=" | "<" | ">" | "<=" | ">=" => {
+                let nums = args.iter().map(|v| Self::as_number(v, span)).collect::<Result<Vec<_>, _>>()?;
+                let cmp: fn(f64, f64) -> bool = match name.as_str() {
+                    "=" => |a, b| a == b,
+                    "<" => |a, b| a < b,
+                    ">" => |a, b| a > b,
+                    "<=" => |a, b| a <= b,
+                    ">=" => |a, b| a >= b,
+                    _ => unreachable!(),
+                };
+                Ok(EnvType::Bool(nums.windows(2).all(|w| cmp(w[0], w[1]))))
+            },
+            // the inverse of `quote`: reconstruct an ast from a value tree and run it
+            "eval" => {
+                let [v] = &args[..] else { return Err(LispError::new("incorrect args", span)); };
+                let ast = AstNode::from_value(v)?;
+                Parser::eval(ast, env_manager, env_id)?.ok_or_else(|| LispError::new("eval produced no value", span))
+            },
+            // call a procedure value with an already-evaluated list of arguments
+            "apply" => {
+                let [proc, list] = &args[..] else { return Err(LispError::new("incorrect args", span)); };
+                let arg_vals = match list {
+                    EnvType::List(items) => items.clone(),
+                    _ => return Err(LispError::new("apply expects a list as its second argument", span)),
+                };
+                match proc {
+                    EnvType::NativeProc(name) => Self::native_call(name, arg_vals, env_manager, env_id, span),
+                    EnvType::Proc(proc) => {
+                        let scope = env_manager.new_env(Some(proc.captured()));
+                        env_manager.push_active(scope);
+                        // bind before collecting: `arg_vals` may hold `Proc`s whose captured env
+                        // isn't otherwise reachable until it's stored into `scope`'s variables
+                        let env = env_manager.get_mut(&scope);
+                        for (k, v) in proc.args().iter().zip(arg_vals) {
+                            env.set(k.clone(), v);
+                        }
+                        env_manager.maybe_collect();
+                        let result = Parser::eval(proc.body(), env_manager, scope);
+                        env_manager.pop_active();
+                        result?.ok_or_else(|| LispError::new("procedure returned no value", span))
+                    },
+                    _ => Err(LispError::new("apply expects a procedure as its first argument", span)),
+                }
             },
-            _ => Err(format!("function {name} not found"))
+            _ => Err(LispError::new(format!("function {name} not found"), span))
         }
     }
 
@@ -0,0 +1,37 @@
+use std::fmt;
+
+// a byte range into the original source string
+#[derive(Debug, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug)]
+pub struct LispError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl LispError {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Self { message: message.into(), span }
+    }
+
+    // render the offending line of `source` with a caret under the span
+    pub fn render(&self, source: &str) -> String {
+        let line_start = source[..self.span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[self.span.start..].find('\n').map_or(source.len(), |i| self.span.start + i);
+        let line = &source[line_start..line_end];
+        let col = self.span.start - line_start;
+        let width = self.span.end.saturating_sub(self.span.start).max(1);
+
+        format!("{line}\n{}{}\nerror: {}", " ".repeat(col), "^".repeat(width), self.message)
+    }
+}
+
+impl fmt::Display for LispError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
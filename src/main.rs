@@ -1,26 +1,37 @@
 use std::collections::VecDeque;
 
 use env::{Env, EnvType, EnvId, EnvManager, ProcInfo};
+use error::{LispError, Span};
 
 mod env;
+mod error;
 
 #[derive(Debug)]
 pub enum Node {
-    Symbol(String),
-    Number(f64),
+    Symbol(String, Span),
+    Number(f64, Span),
 }
 impl Node {
-    fn unwrap_symbol(&self) -> &String {
+    fn span(&self) -> Span {
         match self {
-            Node::Symbol(a) => a,
-            _ => panic!("node is constant"),
+            Node::Symbol(_, s) => *s,
+            Node::Number(_, s) => *s,
         }
     }
 
-    fn resolve<'a>(&self, env_manager: &EnvManager<'a>, env_id: &EnvId) -> Option<EnvType<'a>> {
+    fn try_symbol(&self) -> Result<&String, LispError> {
         match self {
-            Node::Symbol(s) => env_manager.find_var(env_id, s).and_then(|id| env_manager.get(&id).get(s)),
-            Node::Number(c) => Some(EnvType::Number(*c)),
+            Node::Symbol(a, _) => Ok(a),
+            Node::Number(_, span) => Err(LispError::new("expected a symbol", *span)),
+        }
+    }
+
+    fn resolve<'a>(&self, env_manager: &EnvManager<'a>, env_id: &EnvId) -> Result<EnvType<'a>, LispError> {
+        match self {
+            Node::Symbol(s, span) => env_manager.find_var(env_id, s)
+                .and_then(|id| env_manager.get(&id).get(s))
+                .ok_or_else(|| LispError::new(format!("instruction not found: {s}"), *span)),
+            Node::Number(c, _) => Ok(EnvType::Number(*c)),
         }
     }
 }
@@ -28,57 +39,150 @@ impl Node {
 #[derive(Debug)]
 pub enum AstNode {
     Leaf(Node),
-    Body(Vec<AstNode>),
+    Body(Vec<AstNode>, Span),
 }
 impl AstNode {
     fn push(&mut self, a: AstNode) {
         match self {
-            AstNode::Body(n) => n.push(a),
+            AstNode::Body(n, _) => n.push(a),
             _ => panic!("no")
         }
     }
 
-    fn unwrap_leaf(&self) -> &Node {
+    fn span(&self) -> Span {
         match self {
-            AstNode::Leaf(a) => a,
-            _ => panic!("astnode is body"),
+            AstNode::Leaf(n) => n.span(),
+            AstNode::Body(_, span) => *span,
         }
     }
 
     fn unwrap_body(&self) -> &Vec<AstNode> {
         match self {
-            AstNode::Body(a) => a,
+            AstNode::Body(a, _) => a,
             _ => panic!("astnode is leaf"),
         }
     }
+
+    fn try_leaf(&self) -> Result<&Node, LispError> {
+        match self {
+            AstNode::Leaf(a) => Ok(a),
+            AstNode::Body(_, span) => Err(LispError::new("expected a symbol or number, got a list", *span)),
+        }
+    }
+
+    fn try_body(&self) -> Result<&Vec<AstNode>, LispError> {
+        match self {
+            AstNode::Body(a, _) => Ok(a),
+            AstNode::Leaf(n) => Err(LispError::new("expected a list", n.span())),
+        }
+    }
+
+    // check that a special form's body (including its keyword) has exactly `len` elements
+    fn expect_arity(body: &[AstNode], len: usize, form: &str, span: Span) -> Result<(), LispError> {
+        if body.len() == len {
+            Ok(())
+        } else {
+            Err(LispError::new(format!("{form} expects {} argument(s), got {}", len - 1, body.len().saturating_sub(1)), span))
+        }
+    }
+
+    // build a value tree out of an unevaluated ast node, for `quote`
+    fn quote<'a>(&'a self) -> EnvType<'a> {
+        match self {
+            AstNode::Leaf(Node::Symbol(s, _)) => EnvType::Symbol(s.clone()),
+            AstNode::Leaf(Node::Number(n, _)) => EnvType::Number(*n),
+            AstNode::Body(items, _) => EnvType::List(items.iter().map(AstNode::quote).collect()),
+        }
+    }
+
+    // like `quote`, but evaluates and splices in any `(unquote ...)` sub-expression
+    fn quasiquote<'a>(&'a self, env_manager: &mut EnvManager<'a>, env_id: EnvId) -> Result<EnvType<'a>, LispError> {
+        match self {
+            AstNode::Leaf(Node::Symbol(s, _)) => Ok(EnvType::Symbol(s.clone())),
+            AstNode::Leaf(Node::Number(n, _)) => Ok(EnvType::Number(*n)),
+            AstNode::Body(items, span) => {
+                if let Some(AstNode::Leaf(Node::Symbol(head, _))) = items.first() {
+                    if head == "unquote" {
+                        Self::expect_arity(items, 2, "unquote", *span)?;
+                        return Parser::eval(&items[1], env_manager, env_id)?
+                            .ok_or_else(|| LispError::new("unquote produced no value", *span));
+                    }
+                }
+                // root each element as it's produced: an earlier unquoted closure isn't reachable
+                // from any env yet, so a GC triggered by a later unquote must not sweep it
+                let value_root_base = env_manager.value_roots_len();
+                let vals = items.iter()
+                    .map(|n| {
+                        let v = n.quasiquote(env_manager, env_id)?;
+                        env_manager.push_value_root(&v);
+                        Ok(v)
+                    })
+                    .collect::<Result<Vec<_>, LispError>>();
+                env_manager.truncate_value_roots(value_root_base);
+                Ok(EnvType::List(vals?))
+            }
+        }
+    }
+
+    // build an owned ast node out of a value, the inverse of `quote`
+    fn of_value<'a>(val: &EnvType<'a>) -> Result<AstNode, LispError> {
+        let span = Span { start: 0, end: 0 };
+        match val {
+            EnvType::Symbol(s) => Ok(AstNode::Leaf(Node::Symbol(s.clone(), span))),
+            EnvType::Number(n) => Ok(AstNode::Leaf(Node::Number(*n, span))),
+            EnvType::List(items) => Ok(AstNode::Body(items.iter().map(AstNode::of_value).collect::<Result<Vec<_>, _>>()?, span)),
+            _ => Err(LispError::new(format!("cannot eval a value that is not a list, symbol or number: {:?}", val), span)),
+        }
+    }
+
+    // like `of_value`, but leaked to satisfy the same 'a lifetime as the rest of the program's
+    // ast, since this interpreter never frees ast nodes; used by the `eval` native proc to re-enter
+    // `Parser::eval` on a value built at runtime. note this leaks one ast tree per call to `eval` —
+    // acceptable for a toy interpreter, but would need an arena keyed by value to bound memory in
+    // anything longer-running.
+    fn from_value<'a>(val: &EnvType<'a>) -> Result<&'a AstNode, LispError> {
+        Ok(Box::leak(Box::new(Self::of_value(val)?)))
+    }
 }
 
 type Ast = AstNode;
-type Tokens = VecDeque<String>;
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    span: Span,
+}
+type Tokens = VecDeque<Token>;
 
 struct Parser {}
 impl Parser {
     fn tokenise(s: String) -> Tokens {
         let mut tokens = VecDeque::new();
 
-        let mut chars = s.chars();
+        let mut chars = s.char_indices();
         let mut acc = String::new();
-        while let Some(c) = chars.next() {
+        let mut acc_start = 0;
+        while let Some((i, c)) = chars.next() {
             match c {
                 '(' | ')' => {
-                    if acc.len() > 0 {
-                        tokens.push_back(acc);
+                    if !acc.is_empty() {
+                        tokens.push_back(Token { text: acc, span: Span { start: acc_start, end: i } });
                         acc = String::new();
                     }
-                    tokens.push_back(c.to_string());
+                    tokens.push_back(Token { text: c.to_string(), span: Span { start: i, end: i + c.len_utf8() } });
                 }
                 ' ' => {
-                    if acc.len() > 0 {
-                        tokens.push_back(acc);
+                    if !acc.is_empty() {
+                        tokens.push_back(Token { text: acc, span: Span { start: acc_start, end: i } });
                         acc = String::new();
                     }
                 }
-                c => acc.push(c),
+                c => {
+                    if acc.is_empty() {
+                        acc_start = i;
+                    }
+                    acc.push(c);
+                }
             }
         }
 
@@ -88,112 +192,330 @@ impl Parser {
     fn parse(tokens: &mut Tokens) -> Vec<Ast> {
         let mut level = 0;
 
-        let mut stack = VecDeque::from([AstNode::Body(Vec::new())]);
-        while tokens.len() > 0 {
+        let mut starts = VecDeque::from([0]);
+        let mut stack = VecDeque::from([AstNode::Body(Vec::new(), Span { start: 0, end: 0 })]);
+        while !tokens.is_empty() {
             let tok = tokens.pop_front().unwrap();
 
-            match tok.as_str() {
+            match tok.text.as_str() {
                 "(" => {
                     // create new nesting
-                    let ast = AstNode::Body(Vec::new());
-                    stack.push_front(ast);
+                    starts.push_front(tok.span.start);
+                    stack.push_front(AstNode::Body(Vec::new(), tok.span));
                     level += 1;
                 }
                 ")" => {
                     // join into previous nested
-                    let n = stack.pop_front().unwrap();
+                    let start = starts.pop_front().unwrap();
+                    let mut n = stack.pop_front().unwrap();
+                    if let AstNode::Body(_, span) = &mut n {
+                        *span = Span { start, end: tok.span.end };
+                    }
                     stack.front_mut().unwrap().push(n);
                     level -= 1;
                 }
                 _ => {
-                    let t = tok.parse::<f64>().map_or_else(|_| Node::Symbol(tok), |a| Node::Number(a));
+                    let t = tok.text.parse::<f64>().map_or_else(
+                        |_| Node::Symbol(tok.text.clone(), tok.span),
+                        |a| Node::Number(a, tok.span),
+                    );
                     stack.front_mut().unwrap().push(AstNode::Leaf(t))
                 },
             }
         }
 
-        // println!("{stack:#?}");
-
         assert!(level == 0);
 
         match stack.pop_front().unwrap() {
-            AstNode::Body(a) => a,
+            AstNode::Body(a, _) => a,
             _ => { panic!("no"); }
         }
-        // todo!()
     }
 
-    fn eval<'a>(ast: &'a Ast, env_manager: &mut EnvManager<'a>, env_id: EnvId) -> Option<EnvType<'a>> {
-        // let env = env_manager.env(&env_id);
+    // looping trampoline: tail calls into a user proc's body reduce the loop variables instead of
+    // recursing through the native stack, so deep (tail-)recursive lisp programs run in O(1) native
+    // stack frames. non-tail subexpressions (args, define's value, the called proc itself) still
+    // recurse via `Self::eval`.
+    fn eval<'a>(ast: &'a Ast, env_manager: &mut EnvManager<'a>, env_id: EnvId) -> Result<Option<EnvType<'a>>, LispError> {
+        // `env_id` (and whatever the trampoline below reduces onto) is a GC root for as long as
+        // this call is on the native stack; see `EnvManager::push_active`/`maybe_collect`
+        env_manager.push_active(env_id);
+        let result = Self::eval_loop(ast, env_manager, env_id);
+        env_manager.pop_active();
+        result
+    }
 
-        // resolve var if can no longer traverse
-        if let AstNode::Leaf(n) = ast {
-            let val = n.resolve(env_manager, &env_id);
-            if val.is_none() {
-                // string literal not found in env
-                // println!("{env_manager:#?}");
-                panic!("instruction not found: {:?}", n);
+    fn eval_loop<'a>(mut ast: &'a Ast, env_manager: &mut EnvManager<'a>, mut env_id: EnvId) -> Result<Option<EnvType<'a>>, LispError> {
+        loop {
+            // resolve var if can no longer traverse
+            if let AstNode::Leaf(n) = ast {
+                let val = n.resolve(env_manager, &env_id)?;
+                return Ok(Some(val));
             }
-            println!("{:?}: {:?}", n, val);
-            return val;
-        }
 
-        let body = ast.unwrap_body();
+            let body = ast.unwrap_body();
 
-        // handle if first token is keyword
-        let t = body.first().unwrap();
-        if let AstNode::Leaf(_n @ Node::Symbol(n)) = t {
-            match n.as_str() {
-                "define" => {
-                    let v = Self::eval(&body[2], env_manager, env_id).unwrap();
-                    let env = env_manager.get_mut(&env_id);
-                    env.set(body[1].unwrap_leaf().unwrap_symbol().clone(), v);
-                    return None;
-                },
-                "lambda" => {
-                    let args = body[1].unwrap_body().iter().map(|arg| arg.unwrap_leaf().unwrap_symbol().clone()).collect();
-                    return Some(EnvType::Proc(ProcInfo::new(args, &body[2], env_id)));
+            // handle if first token is keyword
+            let t = body.first().ok_or_else(|| LispError::new("cannot evaluate an empty form", ast.span()))?;
+            if let AstNode::Leaf(_n @ Node::Symbol(n, _)) = t {
+                match n.as_str() {
+                    "define" => {
+                        AstNode::expect_arity(body, 3, "define", ast.span())?;
+                        let name = body[1].try_leaf()?.try_symbol()?.clone();
+                        let v = Self::eval(&body[2], env_manager, env_id)?
+                            .ok_or_else(|| LispError::new("define's value produced no result", body[2].span()))?;
+                        let env = env_manager.get_mut(&env_id);
+                        env.set(name, v);
+                        return Ok(None);
+                    },
+                    "lambda" => {
+                        AstNode::expect_arity(body, 3, "lambda", ast.span())?;
+                        let args = body[1].try_body()?.iter()
+                            .map(|arg| arg.try_leaf()?.try_symbol().cloned())
+                            .collect::<Result<Vec<_>, _>>()?;
+                        return Ok(Some(EnvType::Proc(ProcInfo::new(args, &body[2], env_id))));
+                    }
+                    "quote" => {
+                        AstNode::expect_arity(body, 2, "quote", ast.span())?;
+                        return Ok(Some(body[1].quote()));
+                    }
+                    "quasiquote" => {
+                        AstNode::expect_arity(body, 2, "quasiquote", ast.span())?;
+                        return Ok(Some(body[1].quasiquote(env_manager, env_id)?));
+                    }
+                    "unquote" => {
+                        return Err(LispError::new("unquote used outside of quasiquote", ast.span()));
+                    }
+                    "if" => {
+                        if body.len() != 3 && body.len() != 4 {
+                            return Err(LispError::new("if expects a condition, a then-branch, and an optional else-branch", ast.span()));
+                        }
+                        let test = Self::eval(&body[1], env_manager, env_id)?
+                            .ok_or_else(|| LispError::new("if condition produced no value", body[1].span()))?;
+                        ast = if !matches!(test, EnvType::Bool(false)) {
+                            &body[2]
+                        } else if let Some(else_branch) = body.get(3) {
+                            else_branch
+                        } else {
+                            return Ok(None);
+                        };
+                        continue;
+                    }
+                    "cond" => {
+                        let mut next = None;
+                        for clause in &body[1..] {
+                            let items = clause.try_body()?;
+                            if items.len() != 2 {
+                                return Err(LispError::new("cond clause expects a test and a body", clause.span()));
+                            }
+                            let is_else = matches!(&items[0], AstNode::Leaf(Node::Symbol(s, _)) if s == "else");
+                            let truthy = if is_else {
+                                true
+                            } else {
+                                let test = Self::eval(&items[0], env_manager, env_id)?
+                                    .ok_or_else(|| LispError::new("cond test produced no value", items[0].span()))?;
+                                !matches!(test, EnvType::Bool(false))
+                            };
+                            if truthy {
+                                next = Some(&items[1]);
+                                break;
+                            }
+                        }
+                        match next {
+                            Some(next) => { ast = next; continue; },
+                            None => return Ok(None),
+                        }
+                    }
+                    _ => {}
                 }
-                _ => {}
             }
-        }
 
-        let proc_ret = Self::eval(t, env_manager, env_id);
-        // library functions defined outside env
-        if let Some(EnvType::NativeProc(name)) = proc_ret {
-            let args = body[1..].iter().map(|a| Self::eval(a, env_manager, env_id).unwrap()).collect();
-            let ret = Env::native_call(&name, args);
-            return Some(ret.unwrap());
-        } else if let Some(EnvType::Proc(proc)) = proc_ret {
-            let scope = env_manager.new_env(Some(proc.captured()));
-            // no lazy eval :(
-            let arg_vals = body.iter().skip(1).map(|arg| Self::eval(arg, env_manager, env_id).unwrap()).collect::<Vec<_>>();
-
-            let env = env_manager.get_mut(&scope);
-            for (k, v) in proc.args().iter().zip(arg_vals) {
-                env.set(k.clone(), v);
+            let proc_ret = Self::eval(t, env_manager, env_id)?;
+            // library functions defined outside env
+            if let Some(EnvType::NativeProc(name)) = proc_ret {
+                // root each arg as it's produced: an earlier arg's closure isn't reachable from
+                // any env yet, so a GC triggered while reducing a later arg must not sweep it
+                let value_root_base = env_manager.value_roots_len();
+                let args = body[1..].iter()
+                    .map(|a| {
+                        let v = Self::eval(a, env_manager, env_id)?.ok_or_else(|| LispError::new("argument produced no value", a.span()))?;
+                        env_manager.push_value_root(&v);
+                        Ok(v)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                env_manager.truncate_value_roots(value_root_base);
+                let ret = Env::native_call(&name, args, env_manager, env_id, ast.span())?;
+                return Ok(Some(ret));
+            } else if let Some(EnvType::Proc(proc)) = proc_ret {
+                let value_root_base = env_manager.value_roots_len();
+                let scope = env_manager.new_env(Some(proc.captured()));
+                // `scope` isn't on the active stack yet (it only becomes the active scope once we
+                // trampoline onto it below), so pin it directly while args are still being reduced
+                env_manager.push_root_id(scope);
+                // no lazy eval :(
+                let arg_vals = body.iter().skip(1)
+                    .map(|arg| {
+                        let v = Self::eval(arg, env_manager, env_id)?.ok_or_else(|| LispError::new("argument produced no value", arg.span()))?;
+                        env_manager.push_value_root(&v);
+                        Ok(v)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let env = env_manager.get_mut(&scope);
+                for (k, v) in proc.args().iter().zip(arg_vals) {
+                    env.set(k.clone(), v);
+                }
+
+                // tail call: reduce the loop onto the proc's body instead of recursing; keep the
+                // active stack (and GC roots) in sync since this doesn't go through `eval`/`push_active`
+                ast = proc.body();
+                env_id = scope;
+                env_manager.set_active_top(env_id);
+                // `scope` and its args are now reachable via `active`, so the temporary roots above
+                // are no longer needed
+                env_manager.truncate_value_roots(value_root_base);
+                env_manager.maybe_collect();
+                continue;
             }
-            
-            let ret = Self::eval(proc.body(), env_manager, scope);
-            return Some(ret.unwrap());
-        }
 
-        // arbitrarily nested stuff
-        return proc_ret;
+            // arbitrarily nested stuff
+            return Ok(proc_ret);
+        }
     }
 }
 
 fn main() {
     let test = "(define outer (lambda (a) (lambda (b) (* a b)))) ((outer 3) 2) ((outer 3) 3)".to_string();
-    let mut tokens = Parser::tokenise(test);
-    // println!("{tokens:?}");
+    let mut tokens = Parser::tokenise(test.clone());
     let ast = Parser::parse(&mut tokens);
-    // println!("{ast:#?}");
 
     let mut env_manager = EnvManager::new();
     let root_env = env_manager.std_env();
     for n in &ast {
-        let res = Parser::eval(n, &mut env_manager, root_env);
-        println!("{:?}", res);
+        match Parser::eval(n, &mut env_manager, root_env) {
+            Ok(res) => println!("{:?}", res),
+            Err(e) => println!("{}", e.render(&test)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // tokenise + parse + eval a whole program, returning the last form's value
+    fn run(src: &str) -> EnvType<'static> {
+        let mut tokens = Parser::tokenise(src.to_string());
+        let ast = Parser::parse(&mut tokens);
+        let ast: &'static [AstNode] = Box::leak(ast.into_boxed_slice());
+
+        let mut env_manager = EnvManager::new();
+        let root = env_manager.std_env();
+        let mut result = None;
+        for n in ast {
+            result = Parser::eval(n, &mut env_manager, root).expect("eval should not error");
+        }
+        result.expect("expected a final value")
+    }
+
+    // like `run`, but surfaces the last form's `Result` instead of unwrapping it
+    fn try_run(src: &str) -> Result<Option<EnvType<'static>>, LispError> {
+        let mut tokens = Parser::tokenise(src.to_string());
+        let ast = Parser::parse(&mut tokens);
+        let ast: &'static [AstNode] = Box::leak(ast.into_boxed_slice());
+
+        let mut env_manager = EnvManager::new();
+        let root = env_manager.std_env();
+        let mut result = Ok(None);
+        for n in ast {
+            result = Parser::eval(n, &mut env_manager, root);
+            if result.is_err() {
+                break;
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn quote_returns_an_unevaluated_list() {
+        let result = run("(quote (1 2 3))");
+        assert!(matches!(result, EnvType::List(ref items) if items.len() == 3));
+    }
+
+    #[test]
+    fn eval_runs_a_quoted_form() {
+        let result = run("(eval (quote (+ 1 2)))");
+        assert!(matches!(result, EnvType::Number(n) if n == 3.0));
+    }
+
+    #[test]
+    fn eval_of_a_list_holding_a_boolean_errors_instead_of_panicking() {
+        assert!(try_run("(eval (quasiquote ((unquote (< 1 2)))))").is_err());
+    }
+
+    #[test]
+    fn apply_calls_a_procedure_with_a_list_of_args() {
+        let result = run("(apply + (quote (1 2 3)))");
+        assert!(matches!(result, EnvType::Number(n) if n == 6.0));
+    }
+
+    #[test]
+    fn if_only_evaluates_the_taken_branch() {
+        // the else branch names an unbound symbol, so evaluating it would error
+        let result = run("(if (< 1 2) 10 undefined)");
+        assert!(matches!(result, EnvType::Number(n) if n == 10.0));
+    }
+
+    #[test]
+    fn cond_picks_the_first_truthy_clause() {
+        let result = run("(cond ((< 2 1) 1) ((< 1 2) 2) (else 3))");
+        assert!(matches!(result, EnvType::Number(n) if n == 2.0));
+    }
+
+    #[test]
+    fn malformed_forms_error_instead_of_panicking() {
+        assert!(try_run("()").is_err());
+        assert!(try_run("(define x)").is_err());
+        assert!(try_run("(define 5 10)").is_err());
+        assert!(try_run("(quasiquote (unquote))").is_err());
+    }
+
+    #[test]
+    fn recursive_functions_can_resolve_builtins_and_themselves() {
+        let result = run("(define f (lambda (n) (if (< n 2) 1 (* n (f (- n 1)))))) (f 5)");
+        assert!(matches!(result, EnvType::Number(n) if n == 120.0));
+    }
+
+    // a tail-recursive counter deep enough to overflow the native stack if `eval` ever recursed
+    // per call instead of trampolining
+    #[test]
+    fn deep_tail_calls_do_not_grow_the_native_stack() {
+        let result = run("(define count (lambda (n acc) (if (= n 0) acc (count (- n 1) (+ acc 1))))) (count 200000 0)");
+        assert!(matches!(result, EnvType::Number(n) if n == 200000.0));
+    }
+
+    // each tail-recursive step allocates a fresh scope; without the collector running the env
+    // table would grow to hundreds of thousands of entries over this call
+    #[test]
+    fn deep_tail_calls_keep_the_env_table_bounded() {
+        let mut tokens = Parser::tokenise("(define count (lambda (n acc) (if (= n 0) acc (count (- n 1) (+ acc 1))))) (count 200000 0)".to_string());
+        let ast = Parser::parse(&mut tokens);
+        let ast: &'static [AstNode] = Box::leak(ast.into_boxed_slice());
+
+        let mut env_manager = EnvManager::new();
+        let root = env_manager.std_env();
+        for n in ast {
+            Parser::eval(n, &mut env_manager, root).expect("eval should not error");
+        }
+        assert!(env_manager.env_count() < 1024);
+    }
+
+    // `make-adder`'s scope is only reachable through the closure it returns, which then sits
+    // unbound in `use`'s arg list while the second argument recurses deep enough to trigger a
+    // collection; the closure's captured env must survive that window
+    #[test]
+    fn in_flight_closures_survive_a_collection_triggered_by_a_later_argument() {
+        let result = run("(define count (lambda (n acc) (if (= n 0) acc (count (- n 1) (+ acc 1))))) (define make-adder (lambda (x) (lambda (y) (+ x y)))) (define use (lambda (f n) (f n))) (use (make-adder 5) (count 200000 0))");
+        assert!(matches!(result, EnvType::Number(n) if n == 200005.0));
     }
 }